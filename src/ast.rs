@@ -0,0 +1,506 @@
+use std::iter::Peekable;
+
+use crate::error::RegexError;
+
+pub type Phrase = Vec<ReItem>;
+
+/// The chars `\s` matches: ASCII whitespace, compiled down to a plain
+/// `CharClass` since matching is just char-set membership.
+const WHITESPACE_CLASS: &str = " \t\n\r\x0b\x0c";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReItem {
+    Char(char),
+    Digit,
+    Alphanum,
+    CharClass(String),
+    NegCharClass(String),
+    AnchorStart,
+    AnchorEnd,
+    QuantZeroPlus,
+    QuantOnePlus,
+    QuantZeroOrOne,
+    QuantLazyZeroPlus,
+    QuantLazyOnePlus,
+    QuantLazyZeroOrOne,
+    /// `{min,max}`, with `max == usize::MAX` meaning unbounded (`{min,}`).
+    QuantRange(usize, usize, bool),
+    /// `\b`: zero-width, matches where exactly one of the previous and
+    /// next characters is a word character.
+    WordBoundary,
+    /// `\B`: the negation of `WordBoundary`.
+    NonWordBoundary,
+    /// `\p{category}`/`\P{category}` (negated): a Unicode general-category
+    /// class.
+    UnicodeClass(UnicodeCategory, bool),
+    Wildcard,
+    Group(usize, Vec<Phrase>),
+    GroupEnd(usize),
+    Backreference(usize),
+}
+
+/// The Unicode general categories `\p{...}` understands. Deliberately a
+/// small, practical subset rather than the full category table: enough for
+/// common patterns (`\p{L}`, `\p{N}`, `\p{Lu}`, `\p{Ll}`) while staying
+/// implementable against `char`'s own Unicode-aware predicates, with no
+/// external Unicode-category data of our own to maintain.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnicodeCategory {
+    /// `L`: any letter.
+    Letter,
+    /// `Lu`: an uppercase letter.
+    UppercaseLetter,
+    /// `Ll`: a lowercase letter.
+    LowercaseLetter,
+    /// `N`: any number.
+    Number,
+    /// `Z`: a separator (space, line or paragraph).
+    Separator,
+}
+
+impl UnicodeCategory {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "L" => Some(Self::Letter),
+            "Lu" => Some(Self::UppercaseLetter),
+            "Ll" => Some(Self::LowercaseLetter),
+            "N" => Some(Self::Number),
+            "Z" => Some(Self::Separator),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Letter => c.is_alphabetic(),
+            Self::UppercaseLetter => c.is_alphabetic() && c.is_uppercase(),
+            Self::LowercaseLetter => c.is_alphabetic() && c.is_lowercase(),
+            Self::Number => c.is_numeric(),
+            // `char::is_whitespace` also accepts the Cc control chars
+            // (`\t`, `\n`, ...) that make up White_Space; excluding
+            // controls leaves the actual Zs/Zl/Zp separator chars.
+            Self::Separator => c.is_whitespace() && !c.is_control(),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq)]
+enum CompileState {
+    None,
+    Beginning,
+    Escaped,
+    CharClassStart,
+    CharClass(String),
+    NegCharClass(String),
+    Group,
+    Counted(String),
+}
+
+pub struct CompileResult {
+    pub phrases: Vec<Phrase>,
+    pub groups: usize,
+    /// Whether matching should apply simple case folding to `ReItem::Char`
+    /// and character-class membership (the `i` flag).
+    pub case_insensitive: bool,
+}
+
+pub struct ReCompiler {
+    groups: usize,
+    /// Byte length of the pattern, used to report the offset of errors
+    /// detected only once the pattern has been fully consumed (an
+    /// unclosed group, say).
+    len: usize,
+    /// Extended/verbose (`x`) mode: unescaped whitespace is ignored and
+    /// `#` starts a comment running to end-of-line.
+    verbose: bool,
+}
+
+impl ReCompiler {
+    /// Compiles `re`, honoring `verbose`/`case_insensitive` flags passed in
+    /// from outside the pattern (e.g. CLI switches) in addition to a
+    /// leading inline `(?x)`/`(?i)`/`(?xi)` flag group.
+    pub fn compile_with(
+        re: &str,
+        verbose: bool,
+        case_insensitive: bool,
+    ) -> Result<CompileResult, RegexError> {
+        let pattern_len = re.len();
+        let (verbose, case_insensitive, re) = match Self::parse_inline_flags(re) {
+            Some((flags, rest)) => (
+                verbose || flags.contains('x'),
+                case_insensitive || flags.contains('i'),
+                rest,
+            ),
+            None => (verbose, case_insensitive, re),
+        };
+
+        // Error offsets count from the start of the whole pattern, inline
+        // flag group included.
+        let flags_len = pattern_len - re.len();
+        let mut compiler = Self {
+            groups: 0,
+            len: pattern_len,
+            verbose,
+        };
+
+        let mut phrases = Vec::new();
+
+        // Mirrors the `Group` arm's handling of its own `|`-separated
+        // alternatives below: consume the `|` between top-level phrases so
+        // the loop makes progress, and reject a stray `)` with no matching
+        // `(`. A leading/trailing `|` (`a|`, `|a`) is allowed, producing an
+        // empty alternative that matches the empty string, same as `(a|)`
+        // already does inside a group.
+        let mut re_iter = re
+            .char_indices()
+            .map(move |(offset, c)| (offset + flags_len, c))
+            .peekable();
+        if re_iter.peek().is_some() {
+            loop {
+                let phrase = compiler.compile_phrase(&mut re_iter)?;
+                phrases.push(phrase);
+
+                match re_iter.peek() {
+                    Some(&(_, '|')) => {
+                        re_iter.next(); // Consume
+                    }
+                    Some(&(offset, _)) => return Err(RegexError::UnbalancedParen { offset }),
+                    None => break,
+                }
+            }
+        }
+
+        Ok(CompileResult {
+            phrases,
+            groups: compiler.groups,
+            case_insensitive,
+        })
+    }
+
+    /// Recognizes a leading `(?` followed by some combination of `x`/`i`
+    /// flag letters and a closing `)`, returning the flag letters and the
+    /// rest of the pattern. Anything else starting with `(?` is left alone
+    /// and compiled as an ordinary group, where the `?` is then rejected as
+    /// a dangling quantifier.
+    fn parse_inline_flags(re: &str) -> Option<(&str, &str)> {
+        let rest = re.strip_prefix("(?")?;
+        let end = rest.find(')')?;
+        let flags = &rest[..end];
+        if !flags.is_empty() && flags.chars().all(|c| c == 'x' || c == 'i') {
+            Some((flags, &rest[end + 1..]))
+        } else {
+            None
+        }
+    }
+
+    /// Rejects a quantifier (`*`/`+`/`?`/`{n,m}`) that has nothing to
+    /// repeat: either it's the first thing in the phrase, or the previous
+    /// item is itself an anchor or quantifier.
+    fn check_quantifiable(items: &[ReItem], offset: usize) -> Result<(), RegexError> {
+        match items.last() {
+            Some(
+                ReItem::AnchorStart
+                | ReItem::AnchorEnd
+                | ReItem::QuantZeroPlus
+                | ReItem::QuantOnePlus
+                | ReItem::QuantZeroOrOne
+                | ReItem::QuantLazyZeroPlus
+                | ReItem::QuantLazyOnePlus
+                | ReItem::QuantLazyZeroOrOne
+                | ReItem::QuantRange(..),
+            )
+            | None => Err(RegexError::DanglingQuantifier { offset }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Upper bound on a single `{n}`/`{n,m}` count. `compile_range` emits one
+    /// instruction copy per unit of count, so an unbounded count is a
+    /// memory-blowup DoS on patterns like `a{20000000}` (and compounds under
+    /// nesting, e.g. `(a{n}){n}`); this keeps any one repetition's
+    /// instruction cost bounded.
+    const MAX_REPETITION_COUNT: usize = 1000;
+
+    /// Parses the digits between `{` and `}` into a `(min, max)` pair,
+    /// with `max == usize::MAX` standing in for an unbounded `{n,}`.
+    fn parse_counted_range(s: &str, offset: usize) -> Result<(usize, usize), RegexError> {
+        let malformed = || RegexError::MalformedRepetition { offset };
+        let check_count = |count: usize| {
+            if count > Self::MAX_REPETITION_COUNT {
+                Err(RegexError::RepetitionCountTooLarge {
+                    count,
+                    limit: Self::MAX_REPETITION_COUNT,
+                    offset,
+                })
+            } else {
+                Ok(count)
+            }
+        };
+
+        if let Some((min_str, max_str)) = s.split_once(',') {
+            let min: usize = check_count(min_str.parse().map_err(|_| malformed())?)?;
+            let max = if max_str.is_empty() {
+                usize::MAX
+            } else {
+                check_count(max_str.parse().map_err(|_| malformed())?)?
+            };
+            if max < min {
+                return Err(RegexError::InvalidRepetitionRange { min, max, offset });
+            }
+            Ok((min, max))
+        } else {
+            let n: usize = check_count(s.parse().map_err(|_| malformed())?)?;
+            Ok((n, n))
+        }
+    }
+
+    fn compile_phrase<R>(&mut self, re_iter: &mut Peekable<R>) -> Result<Phrase, RegexError>
+    where
+        R: Iterator<Item = (usize, char)>,
+    {
+        let mut items = Vec::new();
+
+        let mut state = CompileState::Beginning;
+        let mut class_start = 0;
+        let mut counted_start = 0;
+        while let Some(&(offset, c)) = re_iter.peek() {
+            if self.verbose && matches!(state, CompileState::None | CompileState::Beginning) {
+                if c.is_whitespace() {
+                    re_iter.next(); // Consume
+                    continue;
+                }
+                if c == '#' {
+                    while let Some(&(_, cc)) = re_iter.peek() {
+                        re_iter.next(); // Consume
+                        if cc == '\n' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            match state {
+                CompileState::None | CompileState::Beginning => match c {
+                    '\\' => state = CompileState::Escaped,
+                    '[' => {
+                        class_start = offset;
+                        state = CompileState::CharClassStart;
+                    }
+                    ']' => return Err(RegexError::UnmatchedCloseBracket { offset }),
+                    '^' if state == CompileState::Beginning => {
+                        items.push(ReItem::AnchorStart);
+                        state = CompileState::None;
+                    }
+                    '$' => items.push(ReItem::AnchorEnd),
+                    '*' => {
+                        Self::check_quantifiable(&items, offset)?;
+                        re_iter.next(); // Consume '*'
+                        let lazy = matches!(re_iter.peek(), Some(&(_, '?')));
+                        if lazy {
+                            re_iter.next(); // Consume '?'
+                            items.push(ReItem::QuantLazyZeroPlus);
+                        } else {
+                            items.push(ReItem::QuantZeroPlus);
+                        }
+                        continue;
+                    }
+                    '+' => {
+                        Self::check_quantifiable(&items, offset)?;
+                        re_iter.next(); // Consume '+'
+                        let lazy = matches!(re_iter.peek(), Some(&(_, '?')));
+                        if lazy {
+                            re_iter.next(); // Consume '?'
+                            items.push(ReItem::QuantLazyOnePlus);
+                        } else {
+                            items.push(ReItem::QuantOnePlus);
+                        }
+                        continue;
+                    }
+                    '?' => {
+                        Self::check_quantifiable(&items, offset)?;
+                        re_iter.next(); // Consume '?'
+                        let lazy = matches!(re_iter.peek(), Some(&(_, '?')));
+                        if lazy {
+                            re_iter.next(); // Consume second '?'
+                            items.push(ReItem::QuantLazyZeroOrOne);
+                        } else {
+                            items.push(ReItem::QuantZeroOrOne);
+                        }
+                        continue;
+                    }
+                    '{' => {
+                        Self::check_quantifiable(&items, offset)?;
+                        counted_start = offset;
+                        state = CompileState::Counted(String::new());
+                    }
+                    '.' => items.push(ReItem::Wildcard),
+                    '(' => state = CompileState::Group,
+                    '|' | ')' => break, // Let parent deal with it, don't consume
+                    _ => items.push(ReItem::Char(c)),
+                },
+                CompileState::Escaped => match c {
+                    'd' => {
+                        items.push(ReItem::Digit);
+                        state = CompileState::None;
+                    }
+                    'w' => {
+                        items.push(ReItem::Alphanum);
+                        state = CompileState::None;
+                    }
+                    'b' => {
+                        items.push(ReItem::WordBoundary);
+                        state = CompileState::None;
+                    }
+                    'B' => {
+                        items.push(ReItem::NonWordBoundary);
+                        state = CompileState::None;
+                    }
+                    's' => {
+                        items.push(ReItem::CharClass(WHITESPACE_CLASS.to_string()));
+                        state = CompileState::None;
+                    }
+                    'p' | 'P' => {
+                        let negated = c == 'P';
+                        re_iter.next(); // Consume 'p'/'P'
+                        if !matches!(re_iter.peek(), Some(&(_, '{'))) {
+                            return Err(RegexError::MalformedUnicodeClass { offset });
+                        }
+                        re_iter.next(); // Consume '{'
+
+                        let mut category = String::new();
+                        loop {
+                            match re_iter.next() {
+                                Some((_, '}')) => break,
+                                Some((_, cc)) => category.push(cc),
+                                None => return Err(RegexError::MalformedUnicodeClass { offset }),
+                            }
+                        }
+                        let Some(category) = UnicodeCategory::parse(&category) else {
+                            return Err(RegexError::InvalidUnicodeCategory { category, offset });
+                        };
+                        items.push(ReItem::UnicodeClass(category, negated));
+                        state = CompileState::None;
+                        continue;
+                    }
+                    '\\' => {
+                        items.push(ReItem::Char(c));
+                        state = CompileState::None;
+                    }
+                    d if ('1'..='9').contains(&d) => {
+                        let index = d.to_digit(10).unwrap() as usize - 1;
+                        if index >= self.groups {
+                            return Err(RegexError::InvalidBackreference { index, offset });
+                        }
+                        items.push(ReItem::Backreference(index));
+                        state = CompileState::None;
+                    }
+                    // A whitespace char or '#' only needs escaping so verbose
+                    // mode doesn't strip it as insignificant/a comment; the
+                    // escape just means "treat literally".
+                    w if w.is_whitespace() => {
+                        items.push(ReItem::Char(w));
+                        state = CompileState::None;
+                    }
+                    '#' => {
+                        items.push(ReItem::Char('#'));
+                        state = CompileState::None;
+                    }
+                    _ => return Err(RegexError::InvalidEscape { escape: c, offset }),
+                },
+                CompileState::CharClassStart => match c {
+                    ']' => state = CompileState::None,
+                    '^' => state = CompileState::NegCharClass(String::new()),
+                    _ => state = CompileState::CharClass(String::from(c)),
+                },
+                CompileState::CharClass(ref mut s) => match c {
+                    ']' => {
+                        let cs = std::mem::replace(&mut state, CompileState::None);
+                        let CompileState::CharClass(cc) = cs else {
+                            unreachable!()
+                        };
+                        items.push(ReItem::CharClass(cc));
+                    }
+                    '\\' => return Err(RegexError::InvalidEscape { escape: c, offset }),
+                    _ => s.push(c),
+                },
+                CompileState::NegCharClass(ref mut s) => match c {
+                    ']' => {
+                        let cs = std::mem::replace(&mut state, CompileState::None);
+                        let CompileState::NegCharClass(cc) = cs else {
+                            unreachable!()
+                        };
+                        items.push(ReItem::NegCharClass(cc));
+                    }
+                    '\\' => return Err(RegexError::InvalidEscape { escape: c, offset }),
+                    _ => s.push(c),
+                },
+                CompileState::Counted(ref mut s) => match c {
+                    '}' => {
+                        let cs = std::mem::replace(&mut state, CompileState::None);
+                        let CompileState::Counted(digits) = cs else {
+                            unreachable!()
+                        };
+                        let (min, max) = Self::parse_counted_range(&digits, counted_start)?;
+
+                        re_iter.next(); // Consume '}'
+                        let lazy = matches!(re_iter.peek(), Some(&(_, '?')));
+                        if lazy {
+                            re_iter.next(); // Consume '?'
+                        }
+                        items.push(ReItem::QuantRange(min, max, !lazy));
+                        continue;
+                    }
+                    '0'..='9' | ',' => s.push(c),
+                    _ => return Err(RegexError::MalformedRepetition { offset: counted_start }),
+                },
+                CompileState::Group => {
+                    let group_n = self.groups;
+                    self.groups += 1;
+
+                    let mut grp = Vec::new();
+                    loop {
+                        let phrase = self.compile_phrase(re_iter)?;
+                        grp.push(phrase);
+
+                        match re_iter.peek() {
+                            Some(&(_, '|')) => {
+                                re_iter.next(); // Consume
+                            }
+                            Some(&(_, ')')) => {
+                                break;
+                            }
+                            Some(&(offset, _)) => {
+                                return Err(RegexError::UnbalancedParen { offset })
+                            }
+                            None => {
+                                return Err(RegexError::UnbalancedParen { offset: self.len })
+                            }
+                        }
+                    }
+
+                    items.push(ReItem::Group(group_n, grp));
+                    state = CompileState::None;
+                }
+            }
+
+            re_iter.next(); // Consume
+        }
+
+        if matches!(
+            state,
+            CompileState::CharClassStart | CompileState::CharClass(_) | CompileState::NegCharClass(_)
+        ) {
+            return Err(RegexError::UnclosedClass {
+                offset: class_start,
+            });
+        }
+        if matches!(state, CompileState::Counted(_)) {
+            return Err(RegexError::UnclosedRepetition {
+                offset: counted_start,
+            });
+        }
+
+        Ok(items)
+    }
+}
@@ -0,0 +1,391 @@
+//! Recursive backtracking matcher, kept as a fallback for patterns the
+//! Pike VM can't represent (backreferences break the "pure NFA" model
+//! because they require remembering captured text, not just positions).
+
+use std::iter::Peekable;
+
+use crate::ast::{CompileResult, Phrase, ReItem};
+use crate::unicode::{class_contains, is_decimal_digit, is_word_char, simple_case_fold};
+
+pub fn match_pattern(text: &str, compile_result: &CompileResult) -> Option<String> {
+    for phrase in compile_result.phrases.iter() {
+        let text_iter = text.chars();
+        let re_iter = phrase.iter().peekable();
+        let matcher = Matcher {
+            text_iter,
+            re_iter,
+            backreferences: vec![Backref::new(); compile_result.groups],
+            matched: String::new(),
+            prev_char: None,
+            case_insensitive: compile_result.case_insensitive,
+        };
+
+        if let Some(result) = matcher.match_phrase() {
+            return Some(result.matched);
+        }
+    }
+
+    None
+}
+
+#[derive(Clone)]
+struct MatchResult<T>
+where
+    T: Clone + Iterator<Item = char>,
+{
+    matched: String,
+    backreferences: Vec<Backref>,
+    remainder: T,
+}
+
+#[derive(Clone)]
+struct Backref {
+    value: String,
+    active: bool, // True if in an active group i.e. matched characters expand the value
+}
+
+impl Backref {
+    fn new() -> Self {
+        Self {
+            value: String::new(),
+            active: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Matcher<'a, T, R>
+where
+    T: Clone + Iterator<Item = char>,
+    R: Clone + Iterator<Item = &'a ReItem>,
+{
+    text_iter: T,
+    re_iter: Peekable<R>,
+    backreferences: Vec<Backref>,
+    matched: String,
+    /// The character just before the current position, used to evaluate
+    /// zero-width word-boundary assertions without consuming input.
+    prev_char: Option<char>,
+    /// Whether `ReItem::Char`/class matching applies simple case folding
+    /// (the `i` flag).
+    case_insensitive: bool,
+}
+
+impl<'a, T, R> Matcher<'a, T, R>
+where
+    T: Clone + Iterator<Item = char>,
+    R: Clone + Iterator<Item = &'a ReItem>,
+{
+    fn into_result(self) -> MatchResult<T> {
+        MatchResult {
+            matched: self.matched,
+            backreferences: self.backreferences,
+            remainder: self.text_iter,
+        }
+    }
+
+    fn match_phrase(mut self) -> Option<MatchResult<T>> {
+        if matches!(self.re_iter.peek(), Some(ReItem::AnchorStart)) {
+            self.re_iter.next(); // Consume
+            self.match_here()
+        } else {
+            loop {
+                let result = self.clone().match_here();
+                if result.is_some() {
+                    return result;
+                } else if let Some(c) = self.text_iter.next() {
+                    self.prev_char = Some(c);
+                } else {
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn match_here(mut self) -> Option<MatchResult<T>> {
+        if let Some(r0) = self.re_iter.next() {
+            if matches!(self.re_iter.peek(), Some(ReItem::QuantZeroPlus)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_greedy(r0, 0, usize::MAX)
+            } else if matches!(self.re_iter.peek(), Some(ReItem::QuantOnePlus)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_greedy(r0, 1, usize::MAX)
+            } else if matches!(self.re_iter.peek(), Some(ReItem::QuantZeroOrOne)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_greedy(r0, 0, 1)
+            } else if matches!(self.re_iter.peek(), Some(ReItem::QuantLazyZeroPlus)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_lazy(r0, 0, usize::MAX)
+            } else if matches!(self.re_iter.peek(), Some(ReItem::QuantLazyOnePlus)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_lazy(r0, 1, usize::MAX)
+            } else if matches!(self.re_iter.peek(), Some(ReItem::QuantLazyZeroOrOne)) {
+                self.re_iter.next(); // Consume
+                self.match_quant_lazy(r0, 0, 1)
+            } else if let Some(&&ReItem::QuantRange(min, max, greedy)) = self.re_iter.peek() {
+                self.re_iter.next(); // Consume
+                if greedy {
+                    self.match_quant_greedy(r0, min, max)
+                } else {
+                    self.match_quant_lazy(r0, min, max)
+                }
+            } else if let ReItem::Group(n, alts) = r0 {
+                self.match_group(*n, alts)
+            } else if let ReItem::GroupEnd(n) = r0 {
+                self.match_group_end(*n)
+            } else if let ReItem::Backreference(backref) = r0 {
+                self.match_backref(*backref)
+            } else if r0 == &ReItem::WordBoundary || r0 == &ReItem::NonWordBoundary {
+                let next_char = self.text_iter.clone().next();
+                let at_boundary = is_word_boundary(self.prev_char, next_char);
+                let wants_boundary = r0 == &ReItem::WordBoundary;
+                if at_boundary == wants_boundary {
+                    self.match_here()
+                } else {
+                    None // No match
+                }
+            } else if let Some(t0) = self.text_iter.next() {
+                if match_char(t0, r0, self.case_insensitive) {
+                    self.matched.push(t0);
+                    for bref in &mut self.backreferences {
+                        if bref.active {
+                            bref.value.push(t0);
+                        }
+                    }
+                    self.prev_char = Some(t0);
+                    self.match_here()
+                } else {
+                    None // No match
+                }
+            } else if r0 == &ReItem::AnchorEnd {
+                // No more input text, but at end so it's a match
+                Some(self.into_result())
+            } else {
+                None // No more input text, no match
+            }
+        } else {
+            // regex is complete
+            Some(self.into_result())
+        }
+    }
+
+    fn match_quant_lazy(self, item: &ReItem, min: usize, max: usize) -> Option<MatchResult<T>> {
+        if min > 0 {
+            return self.match_quant_lazy_expand(item, min, max);
+        }
+
+        let result = self.clone().match_here();
+        if result.is_some() {
+            return result; // Prefer matching as little as possible
+        }
+        self.match_quant_lazy_expand(item, 1, max)
+    }
+
+    /// Tries to match one more `item`, then recurses into
+    /// `match_quant_lazy` with `min`/`max` decremented; used only by the
+    /// lazy quantifiers, both for the mandatory repetitions and for each
+    /// further expansion once stopping early has failed.
+    fn match_quant_lazy_expand(
+        self,
+        item: &ReItem,
+        min: usize,
+        max: usize,
+    ) -> Option<MatchResult<T>> {
+        if max == 0 {
+            return None;
+        }
+
+        let single_matcher = Matcher {
+            text_iter: self.text_iter.clone(),
+            re_iter: std::iter::once(item).peekable(),
+            backreferences: self.backreferences.clone(),
+            matched: String::new(),
+            prev_char: self.prev_char,
+            case_insensitive: self.case_insensitive,
+        };
+        let Some(single_result) = single_matcher.match_here() else {
+            return None; // Can't expand further, nothing else to try
+        };
+
+        let mut matched = self.matched.clone();
+        matched.push_str(&single_result.matched);
+        let prev_char = single_result.matched.chars().last().or(self.prev_char);
+
+        // A zero-width item (e.g. `\b`) matched but consumed no input, so
+        // expanding further would just loop forever without progress.
+        if single_result.matched.is_empty() {
+            let remainder_matcher = Matcher {
+                text_iter: single_result.remainder,
+                re_iter: self.re_iter,
+                backreferences: single_result.backreferences,
+                matched,
+                prev_char,
+                case_insensitive: self.case_insensitive,
+            };
+            return remainder_matcher.match_here();
+        }
+
+        let quant_matcher = Matcher {
+            text_iter: single_result.remainder,
+            re_iter: self.re_iter,
+            backreferences: single_result.backreferences,
+            matched,
+            prev_char,
+            case_insensitive: self.case_insensitive,
+        };
+        quant_matcher.match_quant_lazy(item, min.saturating_sub(1), max.saturating_sub(1))
+    }
+
+    fn match_quant_greedy(self, item: &ReItem, min: usize, max: usize) -> Option<MatchResult<T>> {
+        if max == 0 {
+            return None;
+        }
+
+        let single_matcher = Matcher {
+            text_iter: self.text_iter.clone(),
+            re_iter: std::iter::once(item).peekable(),
+            backreferences: self.backreferences.clone(),
+            matched: String::new(),
+            prev_char: self.prev_char,
+            case_insensitive: self.case_insensitive,
+        };
+        if let Some(single_result) = single_matcher.match_here() {
+            let mut matched = self.matched.clone();
+            matched.push_str(&single_result.matched);
+            let prev_char = single_result.matched.chars().last().or(self.prev_char);
+
+            // A zero-width item (e.g. `\b`) matched but consumed no input;
+            // expanding further would recurse forever for no gain.
+            if single_result.matched.is_empty() {
+                let remainder_matcher = Matcher {
+                    text_iter: single_result.remainder,
+                    re_iter: self.re_iter,
+                    backreferences: single_result.backreferences,
+                    matched,
+                    prev_char,
+                    case_insensitive: self.case_insensitive,
+                };
+                return remainder_matcher.match_here();
+            }
+
+            let quant_matcher = Matcher {
+                text_iter: single_result.remainder.clone(),
+                re_iter: self.re_iter.clone(),
+                backreferences: single_result.backreferences.clone(),
+                matched: matched.clone(),
+                prev_char,
+                case_insensitive: self.case_insensitive,
+            };
+            let quant_result = quant_matcher.match_quant_greedy(
+                item,
+                min.saturating_sub(1),
+                max.saturating_sub(1),
+            );
+            if quant_result.is_some() {
+                quant_result
+            } else {
+                let remainder_matcher = Matcher {
+                    text_iter: single_result.remainder,
+                    re_iter: self.re_iter.clone(),
+                    backreferences: single_result.backreferences,
+                    matched,
+                    prev_char,
+                    case_insensitive: self.case_insensitive,
+                };
+                remainder_matcher.match_here()
+            }
+        } else if min == 0 {
+            self.match_here()
+        } else {
+            None
+        }
+    }
+
+    fn match_group(mut self, n: usize, alts: &'a [Phrase]) -> Option<MatchResult<T>> {
+        self.backreferences[n].active = true;
+
+        for phrase in alts {
+            let mut re_phrase = phrase.clone();
+            re_phrase.push(ReItem::GroupEnd(n));
+            re_phrase.extend(self.re_iter.clone().cloned());
+
+            let phrase_matcher = Matcher {
+                text_iter: self.text_iter.clone(),
+                re_iter: re_phrase.iter().peekable(),
+                backreferences: self.backreferences.clone(),
+                matched: self.matched.clone(),
+                prev_char: self.prev_char,
+                case_insensitive: self.case_insensitive,
+            };
+            let phrase_result = phrase_matcher.match_here();
+            if phrase_result.is_some() {
+                return phrase_result;
+            }
+        }
+
+        self.backreferences[n].active = false;
+
+        None
+    }
+
+    fn match_group_end(mut self, n: usize) -> Option<MatchResult<T>> {
+        self.backreferences[n].active = false;
+        self.match_here()
+    }
+
+    fn match_backref(self, backref: usize) -> Option<MatchResult<T>> {
+        if let Some(bref) = self.backreferences.get(backref) {
+            let mut re_bref: Vec<_> = bref.value.chars().map(ReItem::Char).collect(); // Match the exact text and then the rest
+            re_bref.extend(self.re_iter.clone().cloned());
+            let backref_matcher = Matcher {
+                text_iter: self.text_iter.clone(),
+                re_iter: re_bref.iter().peekable(),
+                backreferences: self.backreferences.clone(),
+                matched: self.matched.clone(),
+                prev_char: self.prev_char,
+                case_insensitive: self.case_insensitive,
+            };
+            backref_matcher.match_here()
+        } else {
+            None
+        }
+    }
+}
+
+fn match_char(text_char: char, re_item: &ReItem, case_insensitive: bool) -> bool {
+    match re_item {
+        ReItem::Char(c) => {
+            *c == text_char
+                || (case_insensitive && simple_case_fold(*c) == simple_case_fold(text_char))
+        }
+        ReItem::Digit => is_decimal_digit(text_char),
+        ReItem::Alphanum => text_char.is_alphanumeric(),
+        ReItem::CharClass(s) => class_contains(s, text_char, case_insensitive),
+        ReItem::NegCharClass(s) => !class_contains(s, text_char, case_insensitive),
+        ReItem::UnicodeClass(category, negated) => category.matches(text_char) != *negated,
+        ReItem::AnchorStart => panic!("Invalid: start anchor not at start"),
+        ReItem::AnchorEnd => false, // Never matches a character
+        ReItem::QuantZeroPlus => panic!("Invalid: quant 0+ not matchable"),
+        ReItem::QuantOnePlus => panic!("Invalid: quant 1+ not matchable"),
+        ReItem::QuantZeroOrOne => panic!("Invalid: quant 0-1 not matchable"),
+        ReItem::QuantLazyZeroPlus => panic!("Invalid: lazy quant 0+ not matchable"),
+        ReItem::QuantLazyOnePlus => panic!("Invalid: lazy quant 1+ not matchable"),
+        ReItem::QuantLazyZeroOrOne => panic!("Invalid: lazy quant 0-1 not matchable"),
+        ReItem::QuantRange(..) => panic!("Invalid: counted quant not matchable"),
+        ReItem::Wildcard => true,
+        ReItem::Group(_, _) => panic!("Invalid: alts not matchable"),
+        ReItem::GroupEnd(_) => panic!("Invalid: end not matchable"),
+        ReItem::Backreference(_) => panic!("Invalid: backreferences not matchable"),
+        ReItem::WordBoundary => panic!("Invalid: word boundary not matchable"),
+        ReItem::NonWordBoundary => panic!("Invalid: non-word boundary not matchable"),
+    }
+}
+
+/// Whether a `\b` word boundary falls between `prev` and `next`: exactly one
+/// of them is a word character (treating "off the edge of the text" as not a
+/// word character, same as Pike VM's `Cursor::at_word_boundary`).
+fn is_word_boundary(prev: Option<char>, next: Option<char>) -> bool {
+    let prev_is_word = prev.is_some_and(is_word_char);
+    let next_is_word = next.is_some_and(is_word_char);
+    prev_is_word != next_is_word
+}
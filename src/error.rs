@@ -0,0 +1,123 @@
+use std::fmt;
+
+/// An error produced while compiling a pattern, together with the byte
+/// offset into the pattern string where it was detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexError {
+    /// A `(` with no matching `)`, or a `)`/end-of-pattern where one was
+    /// expected.
+    UnbalancedParen { offset: usize },
+    /// A `[` with no matching `]`.
+    UnclosedClass { offset: usize },
+    /// A `]` outside of a character class.
+    UnmatchedCloseBracket { offset: usize },
+    /// A `\` followed by a character that isn't a recognized escape.
+    InvalidEscape { escape: char, offset: usize },
+    /// A `*`/`+`/`?` with nothing preceding it to repeat.
+    DanglingQuantifier { offset: usize },
+    /// A `\N` referencing a group number that hasn't been defined.
+    InvalidBackreference { index: usize, offset: usize },
+    /// A `{` with no matching `}`.
+    UnclosedRepetition { offset: usize },
+    /// A `{...}` whose contents aren't a valid `n`, `n,` or `n,m` count.
+    MalformedRepetition { offset: usize },
+    /// A `{n,m}` where `m < n`.
+    InvalidRepetitionRange {
+        min: usize,
+        max: usize,
+        offset: usize,
+    },
+    /// A `\p`/`\P` not followed by a `{category}` group.
+    MalformedUnicodeClass { offset: usize },
+    /// A `\p{category}`/`\P{category}` naming a category we don't
+    /// recognize.
+    InvalidUnicodeCategory { category: String, offset: usize },
+    /// A `{n,m}` (or `{n}`/`{n,}`) whose count exceeds `limit`, which would
+    /// otherwise blow up the compiled program's instruction count.
+    RepetitionCountTooLarge {
+        count: usize,
+        limit: usize,
+        offset: usize,
+    },
+}
+
+impl RegexError {
+    pub fn offset(&self) -> usize {
+        match self {
+            RegexError::UnbalancedParen { offset }
+            | RegexError::UnclosedClass { offset }
+            | RegexError::UnmatchedCloseBracket { offset }
+            | RegexError::InvalidEscape { offset, .. }
+            | RegexError::DanglingQuantifier { offset }
+            | RegexError::InvalidBackreference { offset, .. }
+            | RegexError::UnclosedRepetition { offset }
+            | RegexError::MalformedRepetition { offset }
+            | RegexError::InvalidRepetitionRange { offset, .. }
+            | RegexError::MalformedUnicodeClass { offset }
+            | RegexError::InvalidUnicodeCategory { offset, .. }
+            | RegexError::RepetitionCountTooLarge { offset, .. } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexError::UnbalancedParen { offset } => {
+                write!(f, "unbalanced parenthesis at position {offset}")
+            }
+            RegexError::UnclosedClass { offset } => {
+                write!(f, "unclosed character class starting at position {offset}")
+            }
+            RegexError::UnmatchedCloseBracket { offset } => {
+                write!(f, "unexpected ']' with no matching '[' at position {offset}")
+            }
+            RegexError::InvalidEscape { escape, offset } => {
+                write!(f, "invalid escape sequence '\\{escape}' at position {offset}")
+            }
+            RegexError::DanglingQuantifier { offset } => {
+                write!(f, "quantifier with nothing to repeat at position {offset}")
+            }
+            RegexError::InvalidBackreference { index, offset } => {
+                write!(
+                    f,
+                    "backreference to non-existent group {} at position {offset}",
+                    index + 1
+                )
+            }
+            RegexError::UnclosedRepetition { offset } => {
+                write!(f, "unclosed repetition starting at position {offset}")
+            }
+            RegexError::MalformedRepetition { offset } => {
+                write!(f, "invalid repetition count at position {offset}")
+            }
+            RegexError::InvalidRepetitionRange { min, max, offset } => {
+                write!(
+                    f,
+                    "repetition range {{{min},{max}}} has max < min at position {offset}"
+                )
+            }
+            RegexError::MalformedUnicodeClass { offset } => {
+                write!(f, "expected '{{category}}' after \\p/\\P at position {offset}")
+            }
+            RegexError::InvalidUnicodeCategory { category, offset } => {
+                write!(
+                    f,
+                    "unrecognized Unicode category '{category}' at position {offset}"
+                )
+            }
+            RegexError::RepetitionCountTooLarge {
+                count,
+                limit,
+                offset,
+            } => {
+                write!(
+                    f,
+                    "repetition count {count} exceeds the limit of {limit} at position {offset}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegexError {}
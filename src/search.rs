@@ -0,0 +1,144 @@
+//! A small grep-compatible front end on top of the compile/match pipeline:
+//! reads one or more sources (stdin, or files/directories named on the
+//! command line) and reports per-line matches, ripgrep-style.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::RegexError;
+
+/// The ripgrep-like flags that shape how `run` selects and prints lines.
+pub struct SearchOptions {
+    pub invert: bool,
+    pub line_numbers: bool,
+    pub count_only: bool,
+    pub recursive: bool,
+}
+
+#[derive(Debug)]
+pub enum SearchError {
+    Io(io::Error),
+    Regex(RegexError),
+}
+
+impl From<io::Error> for SearchError {
+    fn from(e: io::Error) -> Self {
+        SearchError::Io(e)
+    }
+}
+
+impl From<RegexError> for SearchError {
+    fn from(e: RegexError) -> Self {
+        SearchError::Regex(e)
+    }
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::Io(e) => write!(f, "{e}"),
+            SearchError::Regex(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Searches `paths` (or stdin when empty) line by line, calling `is_match`
+/// once per line to decide whether it's selected (honoring `options.invert`).
+/// Returns whether any line was selected, which callers use for the
+/// grep-convention exit code: 0 if something matched, 1 otherwise.
+pub fn run(
+    paths: &[String],
+    options: &SearchOptions,
+    mut is_match: impl FnMut(&str) -> Result<bool, RegexError>,
+) -> Result<bool, SearchError> {
+    if paths.is_empty() {
+        let stdin = io::stdin();
+        let lines = stdin.lock().lines();
+        return search_lines(None, lines, options, &mut is_match);
+    }
+
+    let files = collect_files(paths, options.recursive)?;
+    let show_name = files.len() > 1 || options.recursive;
+
+    let mut any_selected = false;
+    for file in files {
+        let contents = fs::read_to_string(&file)?;
+        let lines = contents.lines().map(|l| Ok(l.to_string()));
+        let name = show_name.then(|| file.display().to_string());
+        any_selected |= search_lines(name.as_deref(), lines, options, &mut is_match)?;
+    }
+    Ok(any_selected)
+}
+
+fn search_lines(
+    name: Option<&str>,
+    lines: impl Iterator<Item = io::Result<String>>,
+    options: &SearchOptions,
+    is_match: &mut impl FnMut(&str) -> Result<bool, RegexError>,
+) -> Result<bool, SearchError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let mut any_selected = false;
+    let mut count = 0usize;
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        let selected = is_match(&line)? != options.invert;
+        if !selected {
+            continue;
+        }
+        any_selected = true;
+        count += 1;
+
+        if options.count_only {
+            continue;
+        }
+        if let Some(name) = name {
+            write!(out, "{name}:")?;
+        }
+        if options.line_numbers {
+            write!(out, "{}:", i + 1)?;
+        }
+        writeln!(out, "{line}")?;
+    }
+
+    if options.count_only {
+        match name {
+            Some(name) => writeln!(out, "{name}:{count}")?,
+            None => writeln!(out, "{count}")?,
+        }
+    }
+
+    Ok(any_selected)
+}
+
+fn collect_files(paths: &[String], recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_files_from(Path::new(path), recursive, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn collect_files_from(path: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        if !recursive {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: is a directory", path.display()),
+            ));
+        }
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<_>>()?;
+        entries.sort();
+        for entry in entries {
+            collect_files_from(&entry, recursive, files)?;
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Small Unicode-aware helpers shared by both matching engines (the Pike VM
+//! in `vm.rs` and the backtracker in `backtrack.rs`), so the two don't drift
+//! on what counts as a "word character" or how case folding works.
+
+/// A word character for `\w`/`\b`: Unicode alphanumeric, or underscore.
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// A digit for `\d`: deliberately narrowed to ASCII decimal digits, not the
+/// full Unicode decimal-number (Nd) category (which would also accept
+/// Arabic-Indic `٥`, fullwidth `5`, etc). `char::is_numeric` was tried first
+/// but also accepts non-decimal numerics (Nl/No, e.g. `Ⅻ`/`½`), over-matching
+/// `\d`; std exposes no Nd-only predicate short of vendoring a Unicode
+/// category table, which this crate avoids elsewhere (see
+/// `UnicodeCategory`'s doc comment). ASCII-only is the accepted tradeoff.
+pub(crate) fn is_decimal_digit(c: char) -> bool {
+    c.is_ascii_digit()
+}
+
+/// Simple case folding: maps `c` to a canonical form so that differently
+/// cased versions of the same letter compare equal. Uses the first
+/// character of Rust's (Unicode-aware) lowercase mapping, which covers the
+/// common single-character case pairs without pulling in the full Unicode
+/// `SpecialCasing`/`CaseFolding` tables.
+pub(crate) fn simple_case_fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Whether `c` is a member of a `[...]`-style class (given as its raw
+/// character string), honoring `case_insensitive` via [`simple_case_fold`].
+pub(crate) fn class_contains(class: &str, c: char, case_insensitive: bool) -> bool {
+    if case_insensitive {
+        let folded = simple_case_fold(c);
+        class.chars().any(|cc| simple_case_fold(cc) == folded)
+    } else {
+        class.contains(c)
+    }
+}
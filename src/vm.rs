@@ -0,0 +1,448 @@
+//! Thompson NFA compiler + Pike VM.
+//!
+//! `ReItem` trees are flattened into a linear instruction program, then
+//! run with two ordered thread lists (`clist`/`nlist`) so that matching
+//! stays linear in `text.len() * program.len()` instead of backtracking
+//! exponentially on patterns like `(a*)*`. Each instruction that doesn't
+//! consume a character (`Split`, `Jump`, `Save`, the anchors) is followed
+//! eagerly when a thread is added to a list; a "seen pc" bitset ensures
+//! each pc is only added once per step, which is what bounds the thread
+//! count to `program.len()`.
+
+use crate::ast::{Phrase, ReItem, UnicodeCategory};
+use crate::unicode::{class_contains, is_decimal_digit, is_word_char, simple_case_fold};
+
+#[derive(Clone, Debug)]
+enum CharMatcher {
+    Literal(char),
+    Digit,
+    Alphanum,
+    Class(String),
+    NegClass(String),
+    UnicodeClass(UnicodeCategory, bool),
+    Wildcard,
+}
+
+impl CharMatcher {
+    fn is_match(&self, c: char, case_insensitive: bool) -> bool {
+        match self {
+            CharMatcher::Literal(lit) => {
+                *lit == c || (case_insensitive && simple_case_fold(*lit) == simple_case_fold(c))
+            }
+            CharMatcher::Digit => is_decimal_digit(c),
+            CharMatcher::Alphanum => c.is_alphanumeric(),
+            CharMatcher::Class(s) => class_contains(s, c, case_insensitive),
+            CharMatcher::NegClass(s) => !class_contains(s, c, case_insensitive),
+            CharMatcher::UnicodeClass(category, negated) => category.matches(c) != *negated,
+            CharMatcher::Wildcard => true,
+        }
+    }
+}
+
+fn to_char_matcher(item: &ReItem) -> CharMatcher {
+    match item {
+        ReItem::Char(c) => CharMatcher::Literal(*c),
+        ReItem::Digit => CharMatcher::Digit,
+        ReItem::Alphanum => CharMatcher::Alphanum,
+        ReItem::CharClass(s) => CharMatcher::Class(s.clone()),
+        ReItem::NegCharClass(s) => CharMatcher::NegClass(s.clone()),
+        ReItem::UnicodeClass(category, negated) => CharMatcher::UnicodeClass(*category, *negated),
+        ReItem::Wildcard => CharMatcher::Wildcard,
+        other => unreachable!("{other:?} is not a char-matching item"),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(CharMatcher),
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    AnchorStart,
+    AnchorEnd,
+    WordBoundary,
+    NonWordBoundary,
+    Match,
+}
+
+/// A compiled program, ready to be run by the Pike VM. Slot 0/1 hold the
+/// byte offsets of the overall match; slots `2 + 2*n`/`3 + 2*n` hold
+/// group `n`'s offsets.
+pub struct Program {
+    insts: Vec<Inst>,
+    num_slots: usize,
+    case_insensitive: bool,
+}
+
+impl Program {
+    pub fn compile(phrases: &[Phrase], groups: usize, case_insensitive: bool) -> Self {
+        let mut compiler = ProgramCompiler { insts: Vec::new() };
+        compiler.push(Inst::Save(0));
+        compiler.compile_alts(phrases);
+        compiler.push(Inst::Save(1));
+        compiler.push(Inst::Match);
+
+        Program {
+            insts: compiler.insts,
+            num_slots: 2 + 2 * groups,
+            case_insensitive,
+        }
+    }
+
+    /// Finds the leftmost match, returning the slot offsets (byte offsets
+    /// into `text`) on success.
+    pub fn find(&self, text: &str) -> Option<Vec<Option<usize>>> {
+        let char_indices: Vec<(usize, char)> = text.char_indices().collect();
+        let text_len = text.len();
+
+        let mut clist = ThreadList::new(self.insts.len());
+        let mut nlist = ThreadList::new(self.insts.len());
+        let mut matched: Option<Vec<Option<usize>>> = None;
+
+        let mut pos = 0;
+        loop {
+            let cursor = Cursor {
+                char_indices: &char_indices,
+                text_len,
+                pos,
+            };
+
+            if matched.is_none() {
+                clist.add_thread(&self.insts, 0, vec![None; self.num_slots], cursor);
+            }
+
+            // An empty `clist` only means no *earlier* attempt is still
+            // alive — a zero-width assertion (or `^`) can still make a
+            // later starting position succeed, so only stop once nothing
+            // new can be seeded either (we already matched, or we're at
+            // the last position).
+            if clist.threads.is_empty() && (matched.is_some() || pos >= char_indices.len()) {
+                break;
+            }
+
+            let cur_char = char_indices.get(pos).map(|&(_, c)| c);
+            let next_cursor = Cursor {
+                char_indices: &char_indices,
+                text_len,
+                pos: pos + 1,
+            };
+
+            for i in 0..clist.threads.len() {
+                let thread = &clist.threads[i];
+                match &self.insts[thread.pc] {
+                    Inst::Char(matcher) => {
+                        if let Some(c) = cur_char {
+                            if matcher.is_match(c, self.case_insensitive) {
+                                let slots = thread.slots.clone();
+                                nlist.add_thread(&self.insts, thread.pc + 1, slots, next_cursor);
+                            }
+                        }
+                    }
+                    Inst::Match => {
+                        matched = Some(thread.slots.clone());
+                        break; // Lower-priority threads in clist are discarded.
+                    }
+                    other => unreachable!("{other:?} should have been followed by add_thread"),
+                }
+            }
+
+            std::mem::swap(&mut clist, &mut nlist);
+            nlist.clear();
+
+            if pos >= char_indices.len() {
+                break;
+            }
+            pos += 1;
+        }
+
+        matched
+    }
+}
+
+/// A position within the input, bundled with enough context (the whole
+/// char table) to evaluate zero-width assertions.
+#[derive(Clone, Copy)]
+struct Cursor<'t> {
+    char_indices: &'t [(usize, char)],
+    text_len: usize,
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn byte_offset(&self) -> usize {
+        self.char_indices
+            .get(self.pos)
+            .map_or(self.text_len, |&(b, _)| b)
+    }
+
+    fn at_start(&self) -> bool {
+        self.pos == 0
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.char_indices.len()
+    }
+
+    fn at_word_boundary(&self) -> bool {
+        let prev_is_word = self.pos > 0 && is_word_char(self.char_indices[self.pos - 1].1);
+        let next_is_word = self
+            .char_indices
+            .get(self.pos)
+            .is_some_and(|&(_, c)| is_word_char(c));
+        prev_is_word != next_is_word
+    }
+}
+
+struct Thread {
+    pc: usize,
+    slots: Vec<Option<usize>>,
+}
+
+struct ThreadList {
+    threads: Vec<Thread>,
+    seen: Vec<bool>,
+}
+
+impl ThreadList {
+    fn new(num_insts: usize) -> Self {
+        Self {
+            threads: Vec::new(),
+            seen: vec![false; num_insts],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.threads.clear();
+        self.seen.iter_mut().for_each(|s| *s = false);
+    }
+
+    /// Follows epsilon transitions (everything but `Char`/`Match`) eagerly,
+    /// so only character-consuming or terminal instructions end up queued.
+    fn add_thread(&mut self, insts: &[Inst], pc: usize, mut slots: Vec<Option<usize>>, cursor: Cursor) {
+        if self.seen[pc] {
+            return;
+        }
+        self.seen[pc] = true;
+
+        match &insts[pc] {
+            Inst::Jump(x) => self.add_thread(insts, *x, slots, cursor),
+            Inst::Split(x, y) => {
+                self.add_thread(insts, *x, slots.clone(), cursor);
+                self.add_thread(insts, *y, slots, cursor);
+            }
+            Inst::Save(slot) => {
+                slots[*slot] = Some(cursor.byte_offset());
+                self.add_thread(insts, pc + 1, slots, cursor);
+            }
+            Inst::AnchorStart => {
+                if cursor.at_start() {
+                    self.add_thread(insts, pc + 1, slots, cursor);
+                }
+            }
+            Inst::AnchorEnd => {
+                if cursor.at_end() {
+                    self.add_thread(insts, pc + 1, slots, cursor);
+                }
+            }
+            Inst::WordBoundary => {
+                if cursor.at_word_boundary() {
+                    self.add_thread(insts, pc + 1, slots, cursor);
+                }
+            }
+            Inst::NonWordBoundary => {
+                if !cursor.at_word_boundary() {
+                    self.add_thread(insts, pc + 1, slots, cursor);
+                }
+            }
+            Inst::Char(_) | Inst::Match => self.threads.push(Thread { pc, slots }),
+        }
+    }
+}
+
+struct ProgramCompiler {
+    insts: Vec<Inst>,
+}
+
+impl ProgramCompiler {
+    fn push(&mut self, inst: Inst) -> usize {
+        self.insts.push(inst);
+        self.insts.len() - 1
+    }
+
+    fn compile_alts(&mut self, alts: &[Phrase]) {
+        let Some((last, rest)) = alts.split_last() else {
+            return;
+        };
+
+        let mut jumps_to_end = Vec::new();
+        for alt in rest {
+            let split_idx = self.push(Inst::Split(0, 0));
+            let alt_start = self.insts.len();
+            self.compile_phrase(alt);
+            jumps_to_end.push(self.push(Inst::Jump(0)));
+            let next_alt_start = self.insts.len();
+            self.insts[split_idx] = Inst::Split(alt_start, next_alt_start);
+        }
+        self.compile_phrase(last);
+
+        let end = self.insts.len();
+        for jump_idx in jumps_to_end {
+            self.insts[jump_idx] = Inst::Jump(end);
+        }
+    }
+
+    fn compile_phrase(&mut self, items: &[ReItem]) {
+        let mut iter = items.iter().peekable();
+        while let Some(item) = iter.next() {
+            let quant = match iter.peek() {
+                Some(
+                    ReItem::QuantZeroPlus
+                    | ReItem::QuantOnePlus
+                    | ReItem::QuantZeroOrOne
+                    | ReItem::QuantLazyZeroPlus
+                    | ReItem::QuantLazyOnePlus
+                    | ReItem::QuantLazyZeroOrOne
+                    | ReItem::QuantRange(..),
+                ) => iter.next(),
+                _ => None,
+            };
+
+            match item {
+                ReItem::AnchorStart => {
+                    self.push(Inst::AnchorStart);
+                }
+                ReItem::AnchorEnd => {
+                    self.push(Inst::AnchorEnd);
+                }
+                ReItem::WordBoundary => self.compile_repeat(quant, |c| {
+                    c.push(Inst::WordBoundary);
+                }),
+                ReItem::NonWordBoundary => self.compile_repeat(quant, |c| {
+                    c.push(Inst::NonWordBoundary);
+                }),
+                ReItem::Group(n, alts) => self.compile_repeat(quant, |c| c.compile_group(*n, alts)),
+                ReItem::GroupEnd(_) => unreachable!("GroupEnd only appears in the backtracker's AST"),
+                ReItem::Backreference(_) => unreachable!("backreferences are compiled by the backtracker"),
+                ReItem::QuantZeroPlus
+                | ReItem::QuantOnePlus
+                | ReItem::QuantZeroOrOne
+                | ReItem::QuantLazyZeroPlus
+                | ReItem::QuantLazyOnePlus
+                | ReItem::QuantLazyZeroOrOne
+                | ReItem::QuantRange(..) => {
+                    unreachable!("quantifier with no preceding item")
+                }
+                atom => {
+                    let matcher = to_char_matcher(atom);
+                    self.compile_repeat(quant, |c| {
+                        c.push(Inst::Char(matcher.clone()));
+                    });
+                }
+            }
+        }
+    }
+
+    fn compile_group(&mut self, n: usize, alts: &[Phrase]) {
+        self.push(Inst::Save(2 + 2 * n));
+        self.compile_alts(alts);
+        self.push(Inst::Save(3 + 2 * n));
+    }
+
+    fn compile_repeat(&mut self, quant: Option<&ReItem>, emit_body: impl Fn(&mut Self)) {
+        match quant {
+            None => emit_body(self),
+            Some(ReItem::QuantZeroPlus) => self.compile_star(&emit_body, true),
+            Some(ReItem::QuantLazyZeroPlus) => self.compile_star(&emit_body, false),
+            Some(ReItem::QuantOnePlus) => self.compile_plus(&emit_body, true),
+            Some(ReItem::QuantLazyOnePlus) => self.compile_plus(&emit_body, false),
+            Some(ReItem::QuantZeroOrOne) => self.compile_optional(&emit_body, true),
+            Some(ReItem::QuantLazyZeroOrOne) => self.compile_optional(&emit_body, false),
+            Some(&ReItem::QuantRange(min, max, greedy)) => {
+                self.compile_range(&emit_body, min, max, greedy)
+            }
+            Some(other) => unreachable!("{other:?} is not a quantifier"),
+        }
+    }
+
+    /// `body*`/`body*?`. The branch order in the `Split` encodes priority:
+    /// greedy tries another repetition first, lazy tries exiting first.
+    fn compile_star(&mut self, emit_body: &impl Fn(&mut Self), greedy: bool) {
+        let split_idx = self.push(Inst::Split(0, 0));
+        let body_start = self.insts.len();
+        emit_body(self);
+        self.push(Inst::Jump(split_idx));
+        let after = self.insts.len();
+        self.insts[split_idx] = if greedy {
+            Inst::Split(body_start, after)
+        } else {
+            Inst::Split(after, body_start)
+        };
+    }
+
+    /// `body+`/`body+?`.
+    fn compile_plus(&mut self, emit_body: &impl Fn(&mut Self), greedy: bool) {
+        let body_start = self.insts.len();
+        emit_body(self);
+        let split_idx = self.push(Inst::Split(0, 0));
+        let after = self.insts.len();
+        self.insts[split_idx] = if greedy {
+            Inst::Split(body_start, after)
+        } else {
+            Inst::Split(after, body_start)
+        };
+    }
+
+    /// `body?`/`body??`.
+    fn compile_optional(&mut self, emit_body: &impl Fn(&mut Self), greedy: bool) {
+        let split_idx = self.push(Inst::Split(0, 0));
+        let body_start = self.insts.len();
+        emit_body(self);
+        let after = self.insts.len();
+        self.insts[split_idx] = if greedy {
+            Inst::Split(body_start, after)
+        } else {
+            Inst::Split(after, body_start)
+        };
+    }
+
+    /// `body{min,max}`: `min` mandatory copies, then either an unbounded
+    /// `body*` (when `max` is `usize::MAX`, i.e. `{min,}`) or `max - min`
+    /// independent optional copies for a bounded tail.
+    fn compile_range(&mut self, emit_body: &impl Fn(&mut Self), min: usize, max: usize, greedy: bool) {
+        for _ in 0..min {
+            emit_body(self);
+        }
+        if max == usize::MAX {
+            self.compile_star(emit_body, greedy);
+        } else {
+            for _ in min..max {
+                self.compile_optional(emit_body, greedy);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ReCompiler;
+
+    /// Top-level `|` (`ReCompiler::compile_with` emitting more than one
+    /// phrase) used to hang the compiler forever; guard the alternation
+    /// path end-to-end now that it's fixed.
+    #[test]
+    fn top_level_alternation_matches_either_branch() {
+        let compile_result = ReCompiler::compile_with("a|b", false, false).unwrap();
+        assert_eq!(compile_result.phrases.len(), 2);
+
+        let program = Program::compile(
+            &compile_result.phrases,
+            compile_result.groups,
+            compile_result.case_insensitive,
+        );
+
+        assert!(program.find("a").is_some());
+        assert!(program.find("b").is_some());
+        assert!(program.find("c").is_none());
+    }
+}